@@ -3,7 +3,7 @@
 slint::include_modules!();
 use std::thread;
 use std::sync::{Arc, Mutex};
-use std::collections::HashSet;
+use std::collections::{HashSet, HashMap};
 use std::time::{Instant, Duration};
 use rodio::{OutputStream, Sink, buffer::SamplesBuffer};
 use native_dialog::FileDialog;
@@ -12,24 +12,275 @@ use serde::{Serialize, Deserialize};
 use std::fs;
 use i_slint_backend_winit::WinitWindowAccessor;
 
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct KeySound {
+    key: String,
+    path: String,
+}
 #[derive(Serialize, Deserialize, Debug)]
 struct AppSettings {
     vol: f32, pitch: f32, start: f32, end: f32, delay: f32, slice: f32, path: String,
+    #[serde(default)]
+    rate: f32,
+    #[serde(default)]
+    reverb_mix: f32,
+    #[serde(default)]
+    reverb_delay: f32,
+    #[serde(default)]
+    reverb_feedback: f32,
+    #[serde(default)]
+    key_sounds: Vec<KeySound>,
+}
+/// A sliced sound pool bound to a single key, advanced independently of the
+/// default pool so each key keeps its own position.
+struct KeyPool {
+    chunks: Vec<Vec<i16>>,
+    index: usize,
+    channels: u16,
+    sample_rate: u32,
 }
 struct AudioState {
     chunks: Vec<Vec<i16>>,
     index: usize,
     channels: u16,
     sample_rate: u32,
+    key_pools: HashMap<Key, KeyPool>,
     loudness: f32,
     pitch: f32,
     delay_ms: u64,
     last_played: Instant,
-    pressed_keys: HashSet<Key>, 
+    pressed_keys: HashSet<Key>,
+    samples: Vec<i16>,
+    total_secs: f32,
+}
+
+/// Map a human-readable key name (as shown in the assignment menu) to the
+/// `rdev::Key`(s) it covers. Only the keys worth a dedicated sound are handled;
+/// anything else falls through to the default pool. A plain "shift" binds both
+/// physical shift keys so a right-shift press uses the same assigned sound.
+fn parse_key(name: &str) -> &'static [Key] {
+    match name.to_ascii_lowercase().as_str() {
+        "space" | "spacebar" => &[Key::Space],
+        "enter" | "return" => &[Key::Return],
+        "backspace" => &[Key::Backspace],
+        "tab" => &[Key::Tab],
+        "shift" => &[Key::ShiftLeft, Key::ShiftRight],
+        "shiftleft" => &[Key::ShiftLeft],
+        "shiftright" => &[Key::ShiftRight],
+        _ => &[],
+    }
+}
+
+/// Decode `path` and slice it into playable chunks using the same trim/slice,
+/// resample and reverb rules as the main loader, for sounds bound to an
+/// individual key. `out_rate` of 0 leaves the native rate untouched.
+fn load_chunks(path: &str, clip_start: f32, clip_end: f32, slice_len: f32, out_rate: u32,
+    reverb_mix: f32, reverb_delay: f32, reverb_feedback: f32)
+    -> Option<(Vec<Vec<i16>>, u32, u16)> {
+    let (samples, mut sample_rate, channels) = decode_file(path)?;
+    let sr = sample_rate as f32;
+    let ch = channels as f32;
+    let actual_start = clip_start.min(clip_end);
+    let actual_end = clip_start.max(clip_end);
+    let start_sample = (actual_start * sr * ch) as usize;
+    let end_sample = (actual_end * sr * ch) as usize;
+    let total_samples = end_sample.saturating_sub(start_sample);
+    let mut all: Vec<i16> = samples.into_iter().skip(start_sample).take(total_samples).collect();
+    if all.is_empty() { return None; }
+    if out_rate > 0 && out_rate != sample_rate {
+        all = resample(&all, channels, sample_rate, out_rate);
+        sample_rate = out_rate;
+    }
+    let chunk_size = (slice_len * sample_rate as f32 * ch) as usize;
+    let chunks = all.chunks(chunk_size.max(1))
+        .map(|c| apply_reverb(c, channels, sample_rate, reverb_mix, reverb_delay, reverb_feedback))
+        .collect();
+    Some((chunks, sample_rate, channels))
+}
+
+/// Schroeder-style feedback delay — a lightweight "room" reverb/echo. A
+/// separate circular delay line per channel keeps stereo imaging intact; `mix`
+/// blends dry and wet, `delay_ms` sets the line length, and `feedback` controls
+/// how quickly the repeats decay. The returned buffer is extended by one delay
+/// period so the wet tail that decays past the input isn't truncated.
+fn apply_reverb(samples: &[i16], channels: u16, sample_rate: u32, mix: f32, delay_ms: f32, feedback: f32) -> Vec<i16> {
+    if mix <= 0.0 || delay_ms <= 0.0 { return samples.to_vec(); }
+    let ch = channels.max(1) as usize;
+    let delay_samples = (delay_ms * sample_rate as f32 / 1000.0) as usize;
+    if delay_samples == 0 { return samples.to_vec(); }
+    // Append a silent tail one delay period long so the final echo survives.
+    let mut data = samples.to_vec();
+    data.extend(std::iter::repeat(0).take(delay_samples * ch));
+    let mut buffers = vec![vec![0.0f32; delay_samples]; ch];
+    let mut pos = vec![0usize; ch];
+    for (i, s) in data.iter_mut().enumerate() {
+        let c = i % ch;
+        let x = *s as f32;
+        let p = pos[c];
+        let y = x + feedback * buffers[c][p];
+        buffers[c][p] = y;
+        pos[c] = (p + 1) % delay_samples;
+        let out = (1.0 - mix) * x + mix * y;
+        *s = out.clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+    }
+    data
+}
+
+/// Linearly resample interleaved `samples` from `src_rate` to `dst_rate`.
+///
+/// Channels are kept de-interleaved during interpolation so a stereo file does
+/// not smear one channel into the next. Output length is
+/// `in_len * dst_rate / src_rate`; when `ceil(p)` runs past the last frame it
+/// is clamped to the final frame.
+fn resample(samples: &[i16], channels: u16, src_rate: u32, dst_rate: u32) -> Vec<i16> {
+    if samples.is_empty() || src_rate == 0 || dst_rate == 0 || src_rate == dst_rate {
+        return samples.to_vec();
+    }
+    let ch = channels.max(1) as usize;
+    let in_len = samples.len() / ch;
+    if in_len == 0 { return samples.to_vec(); }
+    let out_len = in_len as u64 * dst_rate as u64 / src_rate as u64;
+    let mut out = Vec::with_capacity(out_len as usize * ch);
+    for i in 0..out_len {
+        let p = i as f64 * src_rate as f64 / dst_rate as f64;
+        let floor = p.floor() as usize;
+        let ceil = (floor + 1).min(in_len - 1);
+        let w = (p - floor as f64) as f32;
+        for c in 0..ch {
+            let a = samples[floor * ch + c] as f32;
+            let b = samples[ceil * ch + c] as f32;
+            out.push((a + (b - a) * w).round() as i16);
+        }
+    }
+    out
+}
+
+/// Peak-pair downsample: bucket the interleaved `samples` into `width` buckets,
+/// keeping the (min, max) amplitude seen across all channels in each bucket so
+/// transients in any channel show up. This stays cheap even for long files.
+fn compute_waveform(samples: &[i16], channels: u16, width: usize) -> Vec<(i16, i16)> {
+    if samples.is_empty() || width == 0 { return Vec::new(); }
+    let ch = channels.max(1) as usize;
+    let frames = samples.len() / ch;
+    if frames == 0 { return Vec::new(); }
+    let mut out = Vec::with_capacity(width);
+    for b in 0..width {
+        let start = b * frames / width;
+        let end = ((b + 1) * frames / width).max(start + 1).min(frames);
+        let mut lo = i16::MAX;
+        let mut hi = i16::MIN;
+        for f in start..end {
+            for c in 0..ch {
+                let s = samples[f * ch + c];
+                lo = lo.min(s);
+                hi = hi.max(s);
+            }
+        }
+        out.push((lo, hi));
+    }
+    out
+}
+
+/// Draw the waveform buckets into an RGBA image, one vertical line per bucket
+/// running from its min to its max amplitude (normalized to ±`i16::MAX`).
+fn render_waveform(buckets: &[(i16, i16)], width: u32, height: u32) -> slint::Image {
+    let mut pixels = slint::SharedPixelBuffer::<slint::Rgba8Pixel>::new(width, height);
+    let w = width as usize;
+    let h = height as f32;
+    let buf = pixels.make_mut_slice();
+    for p in buf.iter_mut() {
+        *p = slint::Rgba8Pixel { r: 0, g: 0, b: 0, a: 0 };
+    }
+    if w == 0 || h < 1.0 { return slint::Image::from_rgba8_premultiplied(pixels); }
+    let mid = h / 2.0;
+    for (x, &(lo, hi)) in buckets.iter().enumerate().take(w) {
+        let top = (mid - (hi as f32 / i16::MAX as f32) * mid).clamp(0.0, h - 1.0) as usize;
+        let bot = (mid - (lo as f32 / i16::MAX as f32) * mid).clamp(0.0, h - 1.0) as usize;
+        for y in top..=bot {
+            buf[y * w + x] = slint::Rgba8Pixel { r: 0x4f, g: 0xc3, b: 0xf7, a: 0xff };
+        }
+    }
+    slint::Image::from_rgba8_premultiplied(pixels)
+}
+
+/// Decode an audio file into a uniform `(samples, sample_rate, channels)` triple.
+///
+/// The format is chosen from the file extension so the rest of the pipeline
+/// (trimming and `chunks()` slicing) can stay format-agnostic. Returns `None`
+/// when the extension is unknown or the file or its header cannot be opened.
+/// Errors after that do not return `None`: a WAV or FLAC sample that fails to
+/// decode becomes 0, and an OGG or MP3 decode error ends the stream early.
+fn decode_file(path: &str) -> Option<(Vec<i16>, u32, u16)> {
+    let ext = std::path::Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+
+    match ext.as_str() {
+        "wav" => {
+            let reader = hound::WavReader::open(path).ok()?;
+            let spec = reader.spec();
+            let samples = reader.into_samples::<i16>().map(|s| s.unwrap_or(0)).collect();
+            Some((samples, spec.sample_rate, spec.channels))
+        }
+        "flac" => {
+            let mut reader = claxon::FlacReader::open(path).ok()?;
+            let info = reader.streaminfo();
+            let bits = info.bits_per_sample as i32;
+            let samples = reader.samples()
+                .map(|s| {
+                    let v = s.unwrap_or(0);
+                    let scaled = if bits > 16 {
+                        v >> (bits - 16)
+                    } else if bits < 16 {
+                        v << (16 - bits)
+                    } else {
+                        v
+                    };
+                    scaled as i16
+                })
+                .collect();
+            Some((samples, info.sample_rate, info.channels as u16))
+        }
+        "ogg" => {
+            let mut srr = lewton::inside_ogg::OggStreamReader::new(fs::File::open(path).ok()?).ok()?;
+            let sample_rate = srr.ident_hdr.audio_sample_rate;
+            let channels = srr.ident_hdr.audio_channels as u16;
+            let mut samples = Vec::new();
+            while let Ok(Some(pck)) = srr.read_dec_packet_itl() {
+                samples.extend(pck);
+            }
+            Some((samples, sample_rate, channels))
+        }
+        "mp3" => {
+            let mut decoder = minimp3::Decoder::new(fs::File::open(path).ok()?);
+            let mut samples = Vec::new();
+            let mut sample_rate = 44100;
+            let mut channels = 2u16;
+            loop {
+                match decoder.next_frame() {
+                    Ok(frame) => {
+                        sample_rate = frame.sample_rate as u32;
+                        channels = frame.channels as u16;
+                        samples.extend(frame.data);
+                    }
+                    Err(minimp3::Error::Eof) => break,
+                    Err(_) => break,
+                }
+            }
+            Some((samples, sample_rate, channels))
+        }
+        _ => None,
+    }
 }
 
 fn main() -> Result<(), slint::PlatformError> {
     let ui = AppWindow::new()?;
+    // Key->sound assignments persisted alongside the scalar settings; shared so
+    // the save callback and the assignment menu both see the live list.
+    let key_sounds: Arc<Mutex<Vec<KeySound>>> = Arc::new(Mutex::new(Vec::new()));
+    let mut restore = (0.0_f32, 0.0_f32, 0.1_f32, 0.0_f32, 0.0_f32, 0.0_f32, 0.0_f32, Vec::<KeySound>::new());
     if let Ok(content) = fs::read_to_string("settings.json") {
         if let Ok(s) = serde_json::from_str::<AppSettings>(&content) {
             ui.set_vol_val(s.vol);
@@ -38,13 +289,26 @@ fn main() -> Result<(), slint::PlatformError> {
             ui.set_end_val(s.end);
             ui.set_delay_val(s.delay);
             ui.set_slice_val(s.slice);
+            ui.set_rate_val(s.rate);
+            ui.set_reverb_mix_val(s.reverb_mix);
+            ui.set_reverb_delay_val(s.reverb_delay);
+            ui.set_reverb_feedback_val(s.reverb_feedback);
             ui.set_selected_path(s.path.into());
+            restore = (s.start, s.end, s.slice, s.rate, s.reverb_mix, s.reverb_delay, s.reverb_feedback, s.key_sounds.clone());
+            *key_sounds.lock().unwrap() = s.key_sounds;
         }
     }
 
     // --- SAVE SETTINGS CALLBACK ---
-    ui.on_save_settings(|vol, pitch, start, end, delay, slice, path| {
-        let s = AppSettings { vol, pitch, start, end, delay, slice, path: path.to_string() };
+    let save_keys = Arc::clone(&key_sounds);
+    ui.on_save_settings(move |vol, pitch, start, end, delay, slice, rate, reverb_mix, reverb_delay, reverb_feedback, path| {
+        let s = AppSettings {
+            vol, pitch, start, end, delay, slice,
+            path: path.to_string(),
+            rate,
+            reverb_mix, reverb_delay, reverb_feedback,
+            key_sounds: save_keys.lock().unwrap().clone(),
+        };
         if let Ok(json) = serde_json::to_string_pretty(&s) {
             let _ = fs::write("settings.json", json);
             println!("Settings saved to settings.json");
@@ -55,13 +319,36 @@ fn main() -> Result<(), slint::PlatformError> {
         index: 0,
         channels: 2,
         sample_rate: 44100,
+        key_pools: HashMap::new(),
         loudness: 1.0,
         pitch: 1.0,
         delay_ms: 50,
         last_played: Instant::now(),
         pressed_keys: HashSet::new(),
+        samples: Vec::new(),
+        total_secs: 0.0,
     }));
 
+    // Restore persisted per-key pools now that the shared state exists.
+    {
+        let (rstart, rend, rslice, rrate, rmix, rdelay, rfeedback, rkeys) = &restore;
+        let mut state = audio_state.lock().unwrap();
+        for ks in rkeys {
+            let keys = parse_key(&ks.key);
+            if keys.is_empty() { continue; }
+            if let Some((chunks, sample_rate, channels)) =
+                load_chunks(&ks.path, *rstart, *rend, *rslice, *rrate as u32, *rmix, *rdelay, *rfeedback)
+            {
+                for &key in keys {
+                    state.key_pools.insert(
+                        key,
+                        KeyPool { chunks: chunks.clone(), index: 0, channels, sample_rate },
+                    );
+                }
+            }
+        }
+    }
+
     // --- KEYBOARD LISTENER ---
     let key_state = Arc::clone(&audio_state);
     thread::spawn(move || {
@@ -76,20 +363,35 @@ fn main() -> Result<(), slint::PlatformError> {
                     if state.pressed_keys.insert(key) {
 
                         if state.last_played.elapsed() >= Duration::from_millis(state.delay_ms) {
-                            if !state.chunks.is_empty() {
-                                let buffer = SamplesBuffer::new(
-                                    state.channels, 
-                                    state.sample_rate, 
-                                    state.chunks[state.index].clone()
-                                );
+                            // Prefer a pool bound to this key, falling back to
+                            // the shared default pool.
+                            let next = if let Some(pool) = state.key_pools.get_mut(&key) {
+                                if pool.chunks.is_empty() {
+                                    None
+                                } else {
+                                    let samples = pool.chunks[pool.index].clone();
+                                    let picked = (pool.channels, pool.sample_rate, samples);
+                                    pool.index = (pool.index + 1) % pool.chunks.len();
+                                    Some(picked)
+                                }
+                            } else if !state.chunks.is_empty() {
+                                let samples = state.chunks[state.index].clone();
+                                let picked = (state.channels, state.sample_rate, samples);
+                                state.index = (state.index + 1) % state.chunks.len();
+                                Some(picked)
+                            } else {
+                                None
+                            };
+
+                            if let Some((channels, sample_rate, samples)) = next {
+                                let buffer = SamplesBuffer::new(channels, sample_rate, samples);
                                 if let Ok(sink) = Sink::try_new(&stream_handle) {
                                     sink.set_volume(state.loudness);
                                     sink.set_speed(state.pitch);
                                     sink.append(buffer);
                                     sink.detach();
-                                    
+
                                     state.last_played = Instant::now();
-                                    state.index = (state.index + 1) % state.chunks.len();
                                 }
                             }
                         }
@@ -107,7 +409,7 @@ fn main() -> Result<(), slint::PlatformError> {
   
     ui.on_browse_file(|| {
         let path = FileDialog::new()
-            .add_filter("WAV Audio", &["wav"])
+            .add_filter("Audio", &["wav", "flac", "ogg", "mp3"])
             .show_open_single_file()
             .unwrap();
         match path {
@@ -118,43 +420,58 @@ fn main() -> Result<(), slint::PlatformError> {
 
     
    let load_state = Arc::clone(&audio_state);
-    ui.on_start_loading(move |vol, pitch, clip_start, clip_end, delay, slice_len, file_path| {
+    ui.on_start_loading(move |vol, pitch, clip_start, clip_end, delay, slice_len, out_rate, reverb_mix, reverb_delay, reverb_feedback, file_path| {
         let path_str = file_path.as_str();
-        let reader = match hound::WavReader::open(path_str) {
-            Ok(r) => r,
-            Err(_) => return,
+        let (samples, mut sample_rate, channels) = match decode_file(path_str) {
+            Some(d) => d,
+            None => return,
         };
-        
-        let spec = reader.spec();
-        let sr = spec.sample_rate as f32;
-        let ch = spec.channels as f32;
-        
+
+        let sr = sample_rate as f32;
+        let ch = channels as f32;
+
+        let full_samples = samples.clone();
+        let total_secs = if sr > 0.0 && ch > 0.0 {
+            full_samples.len() as f32 / (sr * ch)
+        } else {
+            0.0
+        };
+
         let actual_start = clip_start.min(clip_end);
         let actual_end = clip_start.max(clip_end);
-        
+
         let start_sample = (actual_start * sr * ch) as usize;
         let end_sample = (actual_end * sr * ch) as usize;
         let total_samples = end_sample.saturating_sub(start_sample);
 
-        let chunk_size = (slice_len * sr * ch) as usize;
-
-        let all_samples: Vec<i16> = reader.into_samples::<i16>()
+        let mut all_samples: Vec<i16> = samples.into_iter()
             .skip(start_sample)
             .take(total_samples)
-            .map(|s| s.unwrap_or(0))
             .collect();
 
         if all_samples.is_empty() { return; }
 
+        // Optionally resample to the chosen output rate before slicing, so the
+        // queued buffers match the target and stay compact.
+        let dst_rate = out_rate as u32;
+        if dst_rate > 0 && dst_rate != sample_rate {
+            all_samples = resample(&all_samples, channels, sample_rate, dst_rate);
+            sample_rate = dst_rate;
+        }
+
+        let chunk_size = (slice_len * sample_rate as f32 * ch) as usize;
+
         let new_chunks: Vec<Vec<i16>> = all_samples.chunks(chunk_size.max(1))
-            .map(|c| c.to_vec())
+            .map(|c| apply_reverb(c, channels, sample_rate, reverb_mix, reverb_delay, reverb_feedback))
             .collect();
 
         let mut state = load_state.lock().unwrap();
         state.chunks = new_chunks;
         state.index = 0;
-        state.channels = spec.channels;
-        state.sample_rate = spec.sample_rate;
+        state.channels = channels;
+        state.sample_rate = sample_rate;
+        state.samples = full_samples;
+        state.total_secs = total_secs;
         state.loudness = vol;
         state.pitch = pitch;
         state.delay_ms = delay as u64;
@@ -163,6 +480,66 @@ fn main() -> Result<(), slint::PlatformError> {
     }); 
 
     
+    // --- PER-KEY SOUND ASSIGNMENT ---
+    let assign_state = Arc::clone(&audio_state);
+    let assign_keys = Arc::clone(&key_sounds);
+    ui.on_assign_key_sound(move |key_name, clip_start, clip_end, slice_len, out_rate, reverb_mix, reverb_delay, reverb_feedback, file_path| {
+        let name = key_name.to_string();
+        let path = file_path.to_string();
+        let keys = parse_key(&name);
+        if keys.is_empty() { return; }
+        if let Some((chunks, sample_rate, channels)) =
+            load_chunks(&path, clip_start, clip_end, slice_len, out_rate as u32, reverb_mix, reverb_delay, reverb_feedback)
+        {
+            let mut state = assign_state.lock().unwrap();
+            for &key in keys {
+                state.key_pools.insert(
+                    key,
+                    KeyPool { chunks: chunks.clone(), index: 0, channels, sample_rate },
+                );
+            }
+            drop(state);
+            let mut list = assign_keys.lock().unwrap();
+            list.retain(|ks| !ks.key.eq_ignore_ascii_case(&name));
+            list.push(KeySound { key: name, path });
+        }
+    });
+
+    let clear_state = Arc::clone(&audio_state);
+    let clear_keys = Arc::clone(&key_sounds);
+    ui.on_clear_key_sounds(move || {
+        clear_state.lock().unwrap().key_pools.clear();
+        clear_keys.lock().unwrap().clear();
+    });
+
+    // --- WAVEFORM PREVIEW ---
+    // Recomputed whenever a file loads or the window (and thus the preview
+    // width) changes; peak-pair downsampling keeps this cheap for long files.
+    let wave_state = Arc::clone(&audio_state);
+    ui.on_render_waveform(move |width, height| {
+        let state = wave_state.lock().unwrap();
+        let w = width.max(0.0) as u32;
+        let h = height.max(0.0) as u32;
+        let buckets = compute_waveform(&state.samples, state.channels, w as usize);
+        render_waveform(&buckets, w, h)
+    });
+
+    // A marker handle drags along the preview and reports its position as a
+    // 0..1 ratio of the file's duration; translate that back into seconds for
+    // the existing `start_val`/`end_val` properties.
+    let marker_state = Arc::clone(&audio_state);
+    let marker_ui = ui.as_weak();
+    ui.on_set_marker(move |ratio, is_start| {
+        let secs = marker_state.lock().unwrap().total_secs * ratio.clamp(0.0, 1.0);
+        if let Some(ui) = marker_ui.upgrade() {
+            if is_start {
+                ui.set_start_val(secs);
+            } else {
+                ui.set_end_val(secs);
+            }
+        }
+    });
+
     let ui_handle = ui.as_weak();
     ui.on_move_window(move || {
         let ui = ui_handle.unwrap();